@@ -0,0 +1,575 @@
+//! A small JSON5 ("JSON for humans") parser and printer.
+//!
+//! The default `jfmt` pipeline streams through `serde_json::Deserializer` /
+//! `Serializer`, which has no notion of comments or trailing commas and
+//! discards both. `--json5` needs to preserve comments, so this module builds
+//! an actual tree: every node carries the comments that preceded it, and the
+//! printer re-emits them in the same position relative to the value. Trailing
+//! commas are accepted on input but not recorded; the printer adds or omits
+//! them uniformly according to `PrintOptions::trailing_commas`.
+
+use std::fmt::Write as _;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Node>),
+    Object(Vec<(String, Node)>),
+}
+
+/// A `//...` or `/*...*/` comment, kept distinct so the printer can re-emit
+/// each in its own style rather than flattening both into one.
+#[derive(Debug, Clone)]
+pub enum Comment {
+    Line(String),
+    Block(String),
+}
+
+/// A value plus the comments that appeared directly above it in the source
+/// (`leading_comments`) and the comments that trailed it on the way to the
+/// next separator or closing bracket (`trailing_comments`). For the last
+/// element, comments between its `,` and the closing bracket are trailing
+/// comments too. A comment that follows the `,` of any other element (as in
+/// `1, // note` with more elements after it) is collected as a leading
+/// comment of the next element.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub leading_comments: Vec<Comment>,
+    pub value: Value,
+    pub trailing_comments: Vec<Comment>,
+}
+
+/// A parsed document: the root node, plus any comments that trailed after it
+/// with nothing left to attach to.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub root: Node,
+    pub trailing_comments: Vec<Comment>,
+}
+
+#[derive(Debug)]
+pub struct ParseError(String);
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "json5 parse error: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+        }
+    }
+
+    fn err(&self, msg: impl Into<String>) -> ParseError {
+        ParseError(msg.into())
+    }
+
+    /// Consume whitespace and comments, returning the comments collected
+    /// (each entry is one comment, `//...` or `/*...*/`, without the
+    /// delimiters).
+    fn skip_trivia(&mut self) -> Vec<Comment> {
+        let mut comments = Vec::new();
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some('/') => {
+                    let mut lookahead = self.chars.clone();
+                    lookahead.next();
+                    match lookahead.peek() {
+                        Some('/') => {
+                            self.chars.next();
+                            self.chars.next();
+                            let mut text = String::new();
+                            for c in self.chars.by_ref() {
+                                if c == '\n' {
+                                    break;
+                                }
+                                text.push(c);
+                            }
+                            comments.push(Comment::Line(text.trim().to_owned()));
+                        }
+                        Some('*') => {
+                            self.chars.next();
+                            self.chars.next();
+                            let mut text = String::new();
+                            let mut prev = '\0';
+                            for c in self.chars.by_ref() {
+                                if prev == '*' && c == '/' {
+                                    text.pop();
+                                    break;
+                                }
+                                text.push(c);
+                                prev = c;
+                            }
+                            comments.push(Comment::Block(text.trim().to_owned()));
+                        }
+                        _ => break,
+                    }
+                }
+                _ => break,
+            }
+        }
+        comments
+    }
+
+    fn parse_document(&mut self) -> ParseResult<Document> {
+        let leading_comments = self.skip_trivia();
+        let value = self.parse_value()?;
+        let trailing_comments = self.skip_trivia();
+        Ok(Document {
+            root: Node {
+                leading_comments,
+                value,
+                trailing_comments: Vec::new(),
+            },
+            trailing_comments,
+        })
+    }
+
+    fn parse_value(&mut self) -> ParseResult<Value> {
+        match self.chars.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"' | '\'') => self.parse_string().map(Value::String),
+            Some(c) if *c == '-' || c.is_ascii_digit() || *c == '+' || *c == '.' => {
+                self.parse_number().map(Value::Number)
+            }
+            Some(_) => self.parse_keyword(),
+            None => Err(self.err("unexpected end of input")),
+        }
+    }
+
+    fn parse_keyword(&mut self) -> ParseResult<Value> {
+        let mut word = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_alphanumeric() || c == '_' || c == '$' {
+                word.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        match word.as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "null" => Ok(Value::Null),
+            other => Err(self.err(format!("unexpected token `{other}`"))),
+        }
+    }
+
+    fn parse_number(&mut self) -> ParseResult<String> {
+        let mut text = String::new();
+        if matches!(self.chars.peek(), Some('+' | '-')) {
+            text.push(self.chars.next().unwrap());
+        }
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_hexdigit() || matches!(c, '.' | 'x' | 'X' | 'e' | 'E' | '+' | '-') {
+                text.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        if text.is_empty() {
+            return Err(self.err("expected a number"));
+        }
+        Ok(text)
+    }
+
+    /// Parse a single- or double-quoted string, resolving escapes to the
+    /// characters they represent.  Storing the decoded value (rather than
+    /// the raw source text) is what lets the printer re-escape it correctly
+    /// when it re-emits the string in JSON's always-double-quoted style.
+    fn parse_string(&mut self) -> ParseResult<String> {
+        let quote = self.chars.next().expect("peeked quote");
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                Some('\\') => {
+                    let escaped = self.chars.next().ok_or_else(|| self.err("unterminated escape"))?;
+                    match escaped {
+                        '"' => text.push('"'),
+                        '\'' => text.push('\''),
+                        '\\' => text.push('\\'),
+                        '/' => text.push('/'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'v' => text.push('\u{b}'),
+                        '0' => text.push('\0'),
+                        'x' => {
+                            let code: String = self.chars.by_ref().take(2).collect();
+                            let byte = u8::from_str_radix(&code, 16)
+                                .map_err(|_| self.err("invalid \\x escape"))?;
+                            text.push(char::from(byte));
+                        }
+                        '\n' => {} // line continuation: an escaped newline is elided
+                        'u' => {
+                            let mut code = String::new();
+                            for _ in 0..4 {
+                                code.push(
+                                    self.chars
+                                        .next()
+                                        .ok_or_else(|| self.err("unterminated \\u escape"))?,
+                                );
+                            }
+                            let mut code_point = u32::from_str_radix(&code, 16)
+                                .map_err(|_| self.err("invalid \\u escape"))?;
+                            // A high surrogate followed by a `\u` low surrogate is one character.
+                            if (0xD800..0xDC00).contains(&code_point) {
+                                let mut lookahead = self.chars.clone();
+                                if lookahead.next() == Some('\\') && lookahead.next() == Some('u') {
+                                    let low: String = lookahead.by_ref().take(4).collect();
+                                    if let Ok(low @ 0xDC00..=0xDFFF) = u32::from_str_radix(&low, 16) {
+                                        code_point = 0x10000 + ((code_point - 0xD800) << 10) + (low - 0xDC00);
+                                        self.chars = lookahead;
+                                    }
+                                }
+                            }
+                            text.push(char::from_u32(code_point).unwrap_or('\u{fffd}'));
+                        }
+                        other => text.push(other),
+                    }
+                }
+                Some(c) if c == quote => break,
+                Some(c) => text.push(c),
+                None => return Err(self.err("unterminated string")),
+            }
+        }
+        Ok(text)
+    }
+
+    fn parse_identifier_or_string_key(&mut self) -> ParseResult<String> {
+        match self.chars.peek() {
+            Some('"' | '\'') => self.parse_string(),
+            Some(_) => {
+                let mut key = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_alphanumeric() || c == '_' || c == '$' {
+                        key.push(c);
+                        self.chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if key.is_empty() {
+                    Err(self.err("expected an object key"))
+                } else {
+                    Ok(key)
+                }
+            }
+            None => Err(self.err("expected an object key")),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> ParseResult<()> {
+        self.skip_trivia();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.err(format!("expected `{expected}`, found `{c}`"))),
+            None => Err(self.err(format!("expected `{expected}`, found end of input"))),
+        }
+    }
+
+    fn parse_array(&mut self) -> ParseResult<Value> {
+        self.expect('[')?;
+        let mut items: Vec<Node> = Vec::new();
+        loop {
+            let leading_comments = self.skip_trivia();
+            if self.chars.peek() == Some(&']') {
+                self.chars.next();
+                // Comments just consumed have no following element to be
+                // `leading_comments` for — they're inline trivia trailing
+                // the previous item (or are simply lost if the array is
+                // empty), e.g. `[1, // last\n]`.
+                if let Some(last) = items.last_mut() {
+                    last.trailing_comments.extend(leading_comments);
+                }
+                break;
+            }
+            let value = self.parse_value()?;
+            let trailing_comments = self.skip_trivia();
+            items.push(Node {
+                leading_comments,
+                value,
+                trailing_comments,
+            });
+            match self.chars.peek() {
+                Some(',') => {
+                    self.chars.next();
+                }
+                Some(']') => {
+                    self.chars.next();
+                    break;
+                }
+                _ => return Err(self.err("expected `,` or `]` in array")),
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn parse_object(&mut self) -> ParseResult<Value> {
+        self.expect('{')?;
+        let mut entries: Vec<(String, Node)> = Vec::new();
+        loop {
+            let leading_comments = self.skip_trivia();
+            if self.chars.peek() == Some(&'}') {
+                self.chars.next();
+                // See the matching comment in `parse_array`: trivia here
+                // trails the previous member rather than leading a next one
+                // that doesn't exist.
+                if let Some((_, last)) = entries.last_mut() {
+                    last.trailing_comments.extend(leading_comments);
+                }
+                break;
+            }
+            let key = self.parse_identifier_or_string_key()?;
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            let trailing_comments = self.skip_trivia();
+            entries.push((
+                key,
+                Node {
+                    leading_comments,
+                    value,
+                    trailing_comments,
+                },
+            ));
+            match self.chars.peek() {
+                Some(',') => {
+                    self.chars.next();
+                }
+                Some('}') => {
+                    self.chars.next();
+                    break;
+                }
+                _ => return Err(self.err("expected `,` or `}` in object")),
+            }
+        }
+        Ok(Value::Object(entries))
+    }
+}
+
+pub fn parse(input: &str) -> ParseResult<Document> {
+    Parser::new(input).parse_document()
+}
+
+/// Options controlling how a `Document` is re-serialized.
+pub struct PrintOptions {
+    pub indent: String,
+    pub trailing_commas: bool,
+    pub sort_arrays: bool,
+    pub sort_keys: bool,
+    pub one_element_lines: bool,
+}
+
+pub fn print(doc: &Document, opts: &PrintOptions) -> String {
+    let mut out = String::new();
+    print_comments(&doc.root.leading_comments, 0, &mut out, opts);
+    print_value(&doc.root.value, 0, &mut out, opts);
+    print_comments(&doc.trailing_comments, 0, &mut out, opts);
+    out
+}
+
+fn print_comments(comments: &[Comment], depth: usize, out: &mut String, opts: &PrintOptions) {
+    for comment in comments {
+        push_indent(out, depth, opts);
+        print_comment(comment, out);
+        out.push('\n');
+    }
+}
+
+fn print_comment(comment: &Comment, out: &mut String) {
+    match comment {
+        Comment::Line(text) => {
+            out.push_str("// ");
+            out.push_str(text);
+        }
+        Comment::Block(text) => {
+            out.push_str("/*");
+            out.push_str(text);
+            out.push_str("*/");
+        }
+    }
+}
+
+/// Emit comments that trailed a value on its way to the next separator or
+/// closing bracket. The first rides on the end of the current line (the
+/// common `1, // note` style); any further ones get their own indented
+/// line, since nothing may follow a `//` comment on the same line.
+fn print_trailing_comments(comments: &[Comment], depth: usize, out: &mut String, opts: &PrintOptions) {
+    let mut comments = comments.iter();
+    if let Some(first) = comments.next() {
+        out.push(' ');
+        print_comment(first, out);
+    }
+    for comment in comments {
+        out.push('\n');
+        push_indent(out, depth, opts);
+        print_comment(comment, out);
+    }
+}
+
+fn push_indent(out: &mut String, depth: usize, opts: &PrintOptions) {
+    for _ in 0..depth {
+        out.push_str(&opts.indent);
+    }
+}
+
+fn is_scalar(node: &Node) -> bool {
+    !matches!(node.value, Value::Array(_) | Value::Object(_))
+}
+
+fn print_value(value: &Value, depth: usize, out: &mut String, opts: &PrintOptions) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(n),
+        Value::String(s) => print_string(s, out),
+        Value::Array(items) => print_array(items, depth, out, opts),
+        Value::Object(entries) => print_object(entries, depth, out, opts),
+    }
+}
+
+fn print_array(items: &[Node], depth: usize, out: &mut String, opts: &PrintOptions) {
+    if items.is_empty() {
+        out.push_str("[]");
+        return;
+    }
+
+    let mut sorted_storage;
+    let items = if opts.sort_arrays && items.iter().all(is_scalar) {
+        sorted_storage = items.to_vec();
+        sorted_storage.sort_by(|a, b| render_scalar(&a.value).cmp(&render_scalar(&b.value)));
+        &sorted_storage
+    } else {
+        items
+    };
+
+    if opts.one_element_lines
+        && items.len() == 1
+        && items[0].leading_comments.is_empty()
+        && items[0].trailing_comments.is_empty()
+    {
+        out.push('[');
+        print_value(&items[0].value, depth, out, opts);
+        out.push(']');
+        return;
+    }
+
+    out.push_str("[\n");
+    for (i, item) in items.iter().enumerate() {
+        print_comments(&item.leading_comments, depth + 1, out, opts);
+        push_indent(out, depth + 1, opts);
+        print_value(&item.value, depth + 1, out, opts);
+        if i + 1 < items.len() || opts.trailing_commas {
+            out.push(',');
+        }
+        print_trailing_comments(&item.trailing_comments, depth + 1, out, opts);
+        out.push('\n');
+    }
+    push_indent(out, depth, opts);
+    out.push(']');
+}
+
+fn print_object(entries: &[(String, Node)], depth: usize, out: &mut String, opts: &PrintOptions) {
+    if entries.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+
+    if opts.one_element_lines
+        && entries.len() == 1
+        && entries[0].1.leading_comments.is_empty()
+        && entries[0].1.trailing_comments.is_empty()
+    {
+        out.push('{');
+        print_key(&entries[0].0, out);
+        out.push_str(": ");
+        print_value(&entries[0].1.value, depth, out, opts);
+        out.push('}');
+        return;
+    }
+
+    let mut sorted_storage;
+    let entries = if opts.sort_keys {
+        sorted_storage = entries.to_vec();
+        sorted_storage.sort_by(|a, b| a.0.cmp(&b.0));
+        &sorted_storage
+    } else {
+        entries
+    };
+
+    out.push_str("{\n");
+    for (i, (key, node)) in entries.iter().enumerate() {
+        print_comments(&node.leading_comments, depth + 1, out, opts);
+        push_indent(out, depth + 1, opts);
+        print_key(key, out);
+        out.push_str(": ");
+        print_value(&node.value, depth + 1, out, opts);
+        if i + 1 < entries.len() || opts.trailing_commas {
+            out.push(',');
+        }
+        print_trailing_comments(&node.trailing_comments, depth + 1, out, opts);
+        out.push('\n');
+    }
+    push_indent(out, depth, opts);
+    out.push('}');
+}
+
+fn print_key(key: &str, out: &mut String) {
+    print_string(key, out);
+}
+
+/// Emit `s` as a JSON double-quoted string, re-escaping it regardless of how
+/// it was quoted in the source so values containing `"`, `\`, or control
+/// characters round-trip correctly.
+fn print_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn render_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_owned(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.clone(),
+        Value::String(s) => s.clone(),
+        Value::Array(_) | Value::Object(_) => String::new(),
+    }
+}
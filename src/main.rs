@@ -2,16 +2,19 @@
 #![deny(clippy::cargo)]
 #![deny(clippy::nursery)]
 
+mod json5;
+
 use clap::Parser;
 
+use serde::Serialize;
 use serde_json::ser::{CompactFormatter, PrettyFormatter};
-use serde_json::{Deserializer, Serializer};
+use serde_json::{Deserializer, Serializer, Value};
 use serde_transcode::transcode;
 
 use std::borrow::ToOwned;
 use std::fs::{self, create_dir_all, File, OpenOptions};
 use std::io::prelude::*;
-use std::io::{self, stdin, stdout, BufReader, BufWriter};
+use std::io::{self, stdin, stdout, BufReader, BufWriter, IsTerminal};
 use std::path::{Path, PathBuf};
 
 const BACKUP_EXT: &str = ".inplace~";
@@ -53,6 +56,58 @@ impl Write for Output {
     }
 }
 
+/// Produce a minimal unified line diff between `original` and `formatted`,
+/// using a classic LCS alignment so unchanged lines are never reported as
+/// churn.
+fn line_diff(original: &str, formatted: &str) -> String {
+    let orig_lines: Vec<&str> = original.lines().collect();
+    let fmt_lines: Vec<&str> = formatted.lines().collect();
+
+    let n = orig_lines.len();
+    let m = fmt_lines.len();
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if orig_lines[i] == fmt_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if orig_lines[i] == fmt_lines[j] {
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push('-');
+            out.push_str(orig_lines[i]);
+            out.push('\n');
+            i += 1;
+        } else {
+            out.push('+');
+            out.push_str(fmt_lines[j]);
+            out.push('\n');
+            j += 1;
+        }
+    }
+    for line in &orig_lines[i..n] {
+        out.push('-');
+        out.push_str(line);
+        out.push('\n');
+    }
+    for line in &fmt_lines[j..m] {
+        out.push('+');
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    out
+}
+
 enum JSONFormatStyle {
     Compact,
     Pretty(Indentation),
@@ -63,6 +118,22 @@ enum Indentation {
     Tabs,
 }
 
+/// A serde data format `jfmt` knows how to decode from and/or encode to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+    Cbor,
+}
+
+impl DataFormat {
+    /// CBOR is a binary format; refuse to splatter it across a terminal.
+    const fn is_binary(self) -> bool {
+        matches!(self, Self::Cbor)
+    }
+}
+
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
 struct JfmtCliOpts {
@@ -72,9 +143,21 @@ struct JfmtCliOpts {
     compact: bool,
 
     /// Modify INPUT_FILE in-place.  Uses a tempfile+rename for non-destructive failure.
-    #[clap(short, long)]
+    #[clap(short, long, conflicts_with_all = &["check"])]
     in_place: bool,
 
+    /// Check whether INPUT_FILE is already well-formatted.  Prints a diff to
+    /// stderr and exits non-zero if reformatting would change the file;
+    /// writes nothing.  Not compatible with --in-place or --output-file.
+    #[clap(long, conflicts_with_all = &["in_place", "output_file"])]
+    check: bool,
+
+    /// After formatting, keep watching the given file(s)/directories and
+    /// reformat whichever changes.  Only meaningful alongside --in-place or
+    /// --output-file; not compatible with stdin input or --check.
+    #[clap(long, conflicts_with_all = &["check"])]
+    watch: bool,
+
     /// Use the specified number of spaces for indentation.  Must be 1 <= x <= 16,
     /// not compatible with --compact or --tabs.
     #[clap(short, long, conflicts_with_all = &["tabs"])]
@@ -88,35 +171,149 @@ struct JfmtCliOpts {
     #[clap(short, long)]
     output_file: Option<PathBuf>,
 
-    /// Path to read for input.  Use - to read from stdin (default behavior).
-    #[clap(name = "INPUT_FILE", default_value = "-")]
-    input_file: String,
+    /// Format to decode the input as.
+    #[clap(long, value_enum, default_value = "json")]
+    from: DataFormat,
+
+    /// Format to encode the output as.  --spaces/--tabs/--compact only apply
+    /// when this is json.
+    #[clap(long, value_enum, default_value = "json")]
+    to: DataFormat,
+
+    /// Glob pattern to exclude while walking a directory argument.  May be
+    /// repeated.  Matched against each entry as it's visited, so an excluded
+    /// directory's contents are never descended into.  Matched both against
+    /// the full path (e.g. `src/generated/*`) and against the bare entry
+    /// name (e.g. `node_modules` excludes a directory with that name at any
+    /// depth, not just at the top level).
+    #[clap(long)]
+    exclude: Vec<String>,
+
+    /// Extension (without the leading dot) that a file must have to be
+    /// picked up while walking a directory argument.
+    #[clap(long, default_value = "json")]
+    extension: String,
+
+    /// Parse input as JSON5 ("JSON for humans": comments, unquoted keys,
+    /// trailing commas, single-quoted strings) instead of strict JSON,
+    /// preserving comments through reformatting.  Bypasses --from/--to.
+    #[clap(long)]
+    json5: bool,
+
+    /// With --json5, omit the trailing comma that would otherwise be added
+    /// after the last element of a multi-line array or object.
+    #[clap(long, requires = "json5")]
+    no_trailing_commas: bool,
+
+    /// Sort arrays of primitive values lexicographically.
+    #[clap(long)]
+    sort_arrays: bool,
+
+    /// Recursively sort object keys lexicographically before serializing,
+    /// producing canonical, diff-friendly output.  Decodes the whole
+    /// document into a `serde_json::Value` to sort it, rather than
+    /// transcoding it field-by-field like the default path does.
+    #[clap(long)]
+    sort_keys: bool,
+
+    /// With --json5, collapse a single-child object or array onto one line.
+    #[clap(long, requires = "json5")]
+    one_element_lines: bool,
+
+    /// Path(s) to read for input.  Use - to read from stdin (default
+    /// behavior).  A directory is walked recursively for matching files.
+    #[clap(name = "INPUT_FILE", default_value = "-", num_args = 1..)]
+    input_files: Vec<String>,
 }
 
 struct JfmtConfig {
-    pub input: String,
+    pub inputs: Vec<String>,
     pub output: Option<PathBuf>,
     pub in_place: bool,
+    pub check: bool,
+    pub watch: bool,
+    pub from: DataFormat,
+    pub to: DataFormat,
+    pub exclude: Vec<String>,
+    pub extension: String,
+    pub json5: bool,
+    pub no_trailing_commas: bool,
+    pub sort_arrays: bool,
+    pub sort_keys: bool,
+    pub one_element_lines: bool,
     pub format: JSONFormatStyle,
 }
 
-fn pretty_print(
-    input: impl Read,
-    output: &mut impl Write,
-    indent: &str,
-) -> Result<(), serde_json::error::Error> {
-    let mut decoder = Deserializer::from_reader(input);
-    let mut encoder =
-        Serializer::with_formatter(output, PrettyFormatter::with_indent(indent.as_bytes()));
-
-    transcode(&mut decoder, &mut encoder)
+fn to_io_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
 }
 
-fn compact_print(input: impl Read, output: &mut impl Write) -> Result<(), serde_json::error::Error> {
-    let mut decoder = Deserializer::from_reader(input);
-    let mut encoder = Serializer::with_formatter(output, CompactFormatter);
+/// Decode `input` as `from` and re-encode it as `to`, honoring `style` for
+/// the formats (currently only JSON) that support pretty/compact printing.
+fn transcode_document(
+    input: &[u8],
+    output: &mut impl Write,
+    from: DataFormat,
+    to: DataFormat,
+    style: &JSONFormatStyle,
+) -> IOResult<()> {
+    match from {
+        DataFormat::Json => {
+            let mut decoder = Deserializer::from_slice(input);
+            encode_document(&mut decoder, output, to, style)
+        }
+        DataFormat::Yaml => {
+            let mut decoder = serde_yaml::Deserializer::from_slice(input);
+            encode_document(&mut decoder, output, to, style)
+        }
+        DataFormat::Toml => {
+            let text = std::str::from_utf8(input).map_err(to_io_error)?;
+            let mut decoder = toml::Deserializer::new(text);
+            encode_document(&mut decoder, output, to, style)
+        }
+        DataFormat::Cbor => {
+            let mut decoder = serde_cbor::Deserializer::from_slice(input);
+            encode_document(&mut decoder, output, to, style)
+        }
+    }
+}
 
-    transcode(&mut decoder, &mut encoder)
+fn encode_document<'de>(
+    decoder: impl serde::Deserializer<'de>,
+    output: &mut impl Write,
+    to: DataFormat,
+    style: &JSONFormatStyle,
+) -> IOResult<()> {
+    match to {
+        DataFormat::Json => match style {
+            JSONFormatStyle::Compact => {
+                let mut encoder = Serializer::with_formatter(output, CompactFormatter);
+                transcode(decoder, &mut encoder).map_err(to_io_error)
+            }
+            JSONFormatStyle::Pretty(indent) => {
+                let indent = render_indent(indent);
+                let mut encoder =
+                    Serializer::with_formatter(output, PrettyFormatter::with_indent(indent.as_bytes()));
+                transcode(decoder, &mut encoder).map_err(to_io_error)
+            }
+        },
+        DataFormat::Yaml => {
+            let mut encoder = serde_yaml::Serializer::new(output);
+            transcode(decoder, &mut encoder).map_err(to_io_error)
+        }
+        DataFormat::Toml => {
+            let mut text = String::new();
+            {
+                let mut encoder = toml::Serializer::new(&mut text);
+                transcode(decoder, &mut encoder).map_err(to_io_error)?;
+            }
+            output.write_all(text.as_bytes())
+        }
+        DataFormat::Cbor => {
+            let mut encoder = serde_cbor::Serializer::new(output);
+            transcode(decoder, &mut encoder).map_err(to_io_error)
+        }
+    }
 }
 
 fn open_file(name: &str) -> IOResult<File> {
@@ -174,6 +371,83 @@ fn get_temp_file_name(name: &str) -> PathBuf {
     new_name.into()
 }
 
+/// Finish an in-place write by renaming the freshly-written `temp_path` over
+/// `input_path`.  Plain `fs::rename` would replace a symlinked input with a
+/// regular file (breaking the link) and the temp file wouldn't carry the
+/// original's permission bits, so: carry over the original file's mode, and
+/// rename onto its canonicalized (symlink-resolved) target.
+fn finish_in_place_write(temp_path: &Path, input_path: &Path) -> IOResult<()> {
+    let permissions = fs::metadata(input_path)?.permissions();
+    fs::set_permissions(temp_path, permissions)?;
+
+    let real_target = fs::canonicalize(input_path)?;
+    fs::rename(temp_path, real_target)
+}
+
+/// True if `path` matches any of the exclude globs.  Checked at every
+/// directory entry during the walk so an excluded directory's subtree is
+/// skipped rather than expanded and filtered after the fact.
+///
+/// A pattern is matched against the full traversal path (so `**/node_modules`
+/// or `src/generated/*` work as expected) and also against just the entry's
+/// file name (so a bare `--exclude node_modules` excludes a directory with
+/// that name at any depth, not only at the top level).
+fn is_excluded(path: &Path, excludes: &[glob::Pattern]) -> bool {
+    excludes.iter().any(|pat| {
+        pat.matches_path(path)
+            || path
+                .file_name()
+                .is_some_and(|name| pat.matches(&name.to_string_lossy()))
+    })
+}
+
+fn walk_dir(
+    dir: &Path,
+    excludes: &[glob::Pattern],
+    extension: &str,
+    files: &mut Vec<PathBuf>,
+) -> IOResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_excluded(&path, excludes) {
+            continue;
+        }
+
+        if path.is_dir() {
+            walk_dir(&path, excludes, extension, files)?;
+        } else if path.extension().and_then(std::ffi::OsStr::to_str) == Some(extension) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand `inputs` into a flat list of files to format: plain file paths
+/// pass through unchanged, directories are walked recursively for files
+/// with the configured extension, skipping any path matched by `exclude`.
+fn collect_files(inputs: &[String], exclude: &[String], extension: &str) -> IOResult<Vec<PathBuf>> {
+    let excludes: Vec<glob::Pattern> = exclude
+        .iter()
+        .map(|pat| {
+            glob::Pattern::new(pat)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))
+        })
+        .collect::<IOResult<_>>()?;
+
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = PathBuf::from(input);
+        if path.is_dir() {
+            walk_dir(&path, &excludes, extension, &mut files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
 #[allow(dead_code)]
 fn debug_reader(mut reader: impl Read) {
     let mut strbuf = String::new();
@@ -185,12 +459,12 @@ fn debug_reader(mut reader: impl Read) {
 
 fn get_output_file_name(
     in_place: bool,
-    in_file: &Option<File>,
+    is_stdin: bool,
     output: &Option<PathBuf>,
     input: &str,
 ) -> IOResult<Option<PathBuf>> {
-    let name = match (in_place, &in_file, output) {
-        (true, None, _) => {
+    let name = match (in_place, is_stdin, output) {
+        (true, true, _) => {
             eprintln!("Cannot combine stdin with --in-place");
             return Err(io::Error::from(io::ErrorKind::InvalidInput));
         }
@@ -214,9 +488,20 @@ fn parse_cli() -> JfmtConfig {
     };
 
     JfmtConfig {
-        input: cli_opts.input_file,
+        inputs: cli_opts.input_files,
         output: cli_opts.output_file,
         in_place: cli_opts.in_place,
+        check: cli_opts.check,
+        watch: cli_opts.watch,
+        from: cli_opts.from,
+        to: cli_opts.to,
+        exclude: cli_opts.exclude,
+        extension: cli_opts.extension,
+        json5: cli_opts.json5,
+        no_trailing_commas: cli_opts.no_trailing_commas,
+        sort_arrays: cli_opts.sort_arrays,
+        sort_keys: cli_opts.sort_keys,
+        one_element_lines: cli_opts.one_element_lines,
         format,
     }
 }
@@ -238,13 +523,189 @@ fn resolve_indent(opts: &JfmtCliOpts) -> Indentation {
     }
 }
 
-fn real_main() -> IOResult<()> {
-    let cfg = parse_cli();
-    let in_file = get_input_file(&cfg.input)?;
+/// Format `original` per `cfg`, dispatching to the JSON5 tree-based pipeline
+/// when `--json5` is set, to the whole-document sort pipeline when
+/// `--sort-keys`/`--sort-arrays` is set, and to the default transcode
+/// pipeline otherwise. Every path buffers the fully formatted output before
+/// returning it, since callers need the complete bytes to compare against
+/// the original (`--check`) or decide whether a file needs rewriting at all
+/// (`--in-place`, `--watch`).
+fn format_bytes(original: &[u8], cfg: &JfmtConfig) -> IOResult<Vec<u8>> {
+    if cfg.json5 {
+        return format_json5_buffer(original, cfg);
+    }
+    if cfg.sort_keys || cfg.sort_arrays {
+        return format_sorted_buffer(original, cfg);
+    }
+    format_to_buffer(original, cfg.from, cfg.to, &cfg.format)
+}
+
+fn decode_to_value(input: &[u8], from: DataFormat) -> IOResult<Value> {
+    match from {
+        DataFormat::Json => serde_json::from_slice(input).map_err(to_io_error),
+        DataFormat::Yaml => serde_yaml::from_slice(input).map_err(to_io_error),
+        DataFormat::Toml => {
+            let text = std::str::from_utf8(input).map_err(to_io_error)?;
+            toml::from_str(text).map_err(to_io_error)
+        }
+        DataFormat::Cbor => serde_cbor::from_slice(input).map_err(to_io_error),
+    }
+}
+
+fn encode_value(
+    value: &Value,
+    output: &mut impl Write,
+    to: DataFormat,
+    style: &JSONFormatStyle,
+) -> IOResult<()> {
+    match to {
+        DataFormat::Json => match style {
+            JSONFormatStyle::Compact => {
+                let mut encoder = Serializer::with_formatter(output, CompactFormatter);
+                value.serialize(&mut encoder).map_err(to_io_error)
+            }
+            JSONFormatStyle::Pretty(indent) => {
+                let indent = render_indent(indent);
+                let mut encoder =
+                    Serializer::with_formatter(output, PrettyFormatter::with_indent(indent.as_bytes()));
+                value.serialize(&mut encoder).map_err(to_io_error)
+            }
+        },
+        DataFormat::Yaml => {
+            let mut encoder = serde_yaml::Serializer::new(output);
+            value.serialize(&mut encoder).map_err(to_io_error)
+        }
+        DataFormat::Toml => {
+            let mut text = String::new();
+            {
+                let mut encoder = toml::Serializer::new(&mut text);
+                value.serialize(&mut encoder).map_err(to_io_error)?;
+            }
+            output.write_all(text.as_bytes())
+        }
+        DataFormat::Cbor => {
+            let mut encoder = serde_cbor::Serializer::new(output);
+            value.serialize(&mut encoder).map_err(to_io_error)
+        }
+    }
+}
+
+/// Recursively sorts object keys when `sort_keys` is set, and sorts arrays
+/// whose elements are all scalars when `sort_arrays` is set. The two flags
+/// are independent: either can be passed without the other.
+fn sort_value(value: Value, sort_keys: bool, sort_arrays: bool) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map
+                .into_iter()
+                .map(|(k, v)| (k, sort_value(v, sort_keys, sort_arrays)))
+                .collect();
+            if sort_keys {
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+            Value::Object(entries.into_iter().collect())
+        }
+        Value::Array(items) => {
+            let mut items: Vec<Value> = items
+                .into_iter()
+                .map(|v| sort_value(v, sort_keys, sort_arrays))
+                .collect();
+            if sort_arrays && items.iter().all(|v| !matches!(v, Value::Object(_) | Value::Array(_))) {
+                items.sort_by(|a, b| a.to_string().cmp(&b.to_string()));
+            }
+            Value::Array(items)
+        }
+        other => other,
+    }
+}
+
+/// `--sort-keys`/`--sort-arrays` path: deserializes the whole document into
+/// a `Value`, sorts it, and re-serializes. Used in place of the default
+/// transcode, which moves values from decoder to encoder without ever
+/// materializing them and so has no whole-document view to sort with.
+fn format_sorted_buffer(original: &[u8], cfg: &JfmtConfig) -> IOResult<Vec<u8>> {
+    let value = decode_to_value(original, cfg.from)?;
+    let sorted = sort_value(value, cfg.sort_keys, cfg.sort_arrays);
+
+    let mut buf = Vec::new();
+    encode_value(&sorted, &mut buf, cfg.to, &cfg.format)?;
+    if !cfg.to.is_binary() {
+        buf.push(b'\n');
+    }
+    Ok(buf)
+}
+
+fn format_json5_buffer(original: &[u8], cfg: &JfmtConfig) -> IOResult<Vec<u8>> {
+    let text = std::str::from_utf8(original).map_err(to_io_error)?;
+    let doc = json5::parse(text).map_err(to_io_error)?;
+
+    let indent = match &cfg.format {
+        JSONFormatStyle::Pretty(indent) => render_indent(indent),
+        JSONFormatStyle::Compact => String::new(),
+    };
+    let opts = json5::PrintOptions {
+        indent,
+        trailing_commas: !cfg.no_trailing_commas,
+        sort_arrays: cfg.sort_arrays,
+        sort_keys: cfg.sort_keys,
+        one_element_lines: cfg.one_element_lines,
+    };
+
+    let mut rendered = json5::print(&doc, &opts);
+    rendered.push('\n');
+    Ok(rendered.into_bytes())
+}
+
+fn format_to_buffer(
+    original: &[u8],
+    from: DataFormat,
+    to: DataFormat,
+    format: &JSONFormatStyle,
+) -> IOResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    transcode_document(original, &mut buf, from, to, format)?;
+
+    // Make sure we write a newline at the end of the stream.  Not meaningful
+    // for a binary format like CBOR.
+    // It's not FULLY cross-platform, but this works for MOST cases on every
+    // platform I know of, including windows. Even notepad.exe supports it now.
+    if !to.is_binary() {
+        buf.push(b'\n');
+    }
+
+    Ok(buf)
+}
+
+fn run_check(original: &[u8], formatted: &[u8]) -> IOResult<()> {
+    if original == formatted {
+        return Ok(());
+    }
+
+    let original_str = String::from_utf8_lossy(original);
+    let formatted_str = String::from_utf8_lossy(formatted);
+    eprint!("{}", line_diff(&original_str, &formatted_str));
+    eprintln!("error: input would be reformatted");
+
+    std::process::exit(1);
+}
+
+/// The original single-stream pipeline: one input (file or stdin), written
+/// to stdout, an --output-file, or back in-place.
+fn format_single_stream(input: &str, cfg: &JfmtConfig) -> IOResult<()> {
+    let is_stdin = input == "-";
+    let in_file = get_input_file(input)?;
+
+    let mut original = Vec::new();
+    get_reader(in_file).read_to_end(&mut original)?;
+    let formatted = format_bytes(&original, cfg)?;
+
+    if cfg.check {
+        return run_check(&original, &formatted);
+    }
+
     let out_file_name: Option<PathBuf> =
-        get_output_file_name(cfg.in_place, &in_file, &cfg.output, &cfg.input)?;
+        get_output_file_name(cfg.in_place, is_stdin, &cfg.output, input)?;
 
-    let reader = get_reader(in_file);
     let mut writer = match &out_file_name {
         None => get_writer(None),
         Some(x) => {
@@ -253,24 +714,180 @@ fn real_main() -> IOResult<()> {
         }
     };
 
-    match cfg.format {
-        JSONFormatStyle::Compact => compact_print(reader, &mut writer),
-        JSONFormatStyle::Pretty(indent) => pretty_print(reader, &mut writer, &render_indent(&indent)),
-    }?;
-
-    // Make sure we write a newline at the end of the stream.
-    // It's not FULLY cross-platform, but this works for MOST cases on every
-    // platform I know of, including windows. Even notepad.exe supports it now.
-    writer.write_all(b"\n")?;
+    writer.write_all(&formatted)?;
 
     if cfg.in_place {
         let out_file_name = out_file_name.unwrap();
-        fs::rename(&out_file_name, cfg.input)?;
+        // Flush and close the temp file before renaming: on Windows a
+        // rename can't replace a file that's still open.
+        writer.flush()?;
+        drop(writer);
+        finish_in_place_write(&out_file_name, Path::new(input))?;
     };
 
     Ok(())
 }
 
+/// Format a single file discovered while walking a directory argument (or
+/// re-discovered by `--watch`).  Always goes through the tempfile+rename
+/// in-place path; returns whether the file differed from its formatted
+/// version, i.e. whether it was (or, under `--check`, would have been)
+/// reformatted.
+fn format_one_file(path: &Path, cfg: &JfmtConfig) -> IOResult<bool> {
+    let original = fs::read(path)?;
+    let formatted = format_bytes(&original, cfg)?;
+
+    if original == formatted {
+        return Ok(false);
+    }
+
+    if cfg.check {
+        let original_str = String::from_utf8_lossy(&original);
+        let formatted_str = String::from_utf8_lossy(&formatted);
+        eprint!("{}", line_diff(&original_str, &formatted_str));
+        return Ok(true);
+    }
+
+    let path_str = path.to_string_lossy().into_owned();
+    let temp_name = get_temp_file_name(&path_str);
+    let mut out_file = open_output_file(&temp_name, false)?;
+    out_file.write_all(&formatted)?;
+    // Flush and close the temp file before renaming: on Windows a rename
+    // can't replace a file that's still open.
+    out_file.flush()?;
+    drop(out_file);
+    finish_in_place_write(&temp_name, path)?;
+
+    Ok(true)
+}
+
+fn real_main() -> IOResult<()> {
+    let cfg = parse_cli();
+
+    if cfg.watch && cfg.inputs.iter().any(|i| i == "-") {
+        eprintln!("Cannot combine stdin with --watch");
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+
+    if cfg.watch && !cfg.in_place && cfg.output.is_none() {
+        eprintln!("--watch requires --in-place or --output-file (there's nowhere to put repeated reformats otherwise)");
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+
+    if cfg.to.is_binary() && cfg.output.is_none() && !cfg.in_place && stdout().is_terminal() {
+        eprintln!("Refusing to write binary {:?} output to a terminal", cfg.to);
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+
+    let is_directory_mode = cfg.inputs.len() > 1 || cfg.inputs.iter().any(|i| Path::new(i).is_dir());
+
+    if is_directory_mode {
+        if !cfg.in_place && !cfg.check {
+            eprintln!("Formatting multiple paths/directories requires --in-place or --check");
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        if cfg.output.is_some() {
+            eprintln!("Cannot combine --output-file with multiple paths/directories");
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+
+        let files = collect_files(&cfg.inputs, &cfg.exclude, &cfg.extension)?;
+        let mut needs_reformat = false;
+        for file in &files {
+            if format_one_file(file, &cfg)? && cfg.check {
+                eprintln!("would reformat {}", file.display());
+                needs_reformat = true;
+            }
+        }
+
+        if cfg.check && needs_reformat {
+            std::process::exit(1);
+        }
+    } else {
+        format_single_stream(&cfg.inputs[0], &cfg)?;
+    }
+
+    if cfg.watch {
+        run_watch(&cfg, is_directory_mode)?;
+    }
+
+    Ok(())
+}
+
+/// After the initial formatting pass, keep reformatting whichever watched
+/// file changes.  Events are debounced so a burst of writes from an editor
+/// collapses into a single reformat.
+fn run_watch(cfg: &JfmtConfig, is_directory_mode: bool) -> IOResult<()> {
+    use notify::{RecursiveMode, Watcher};
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(move |res| {
+        let _ = tx.send(res);
+    })
+    .map_err(to_io_error)?;
+
+    for input in &cfg.inputs {
+        let path = Path::new(input);
+        let mode = if path.is_dir() {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(path, mode).map_err(to_io_error)?;
+    }
+
+    eprintln!("Watching for changes. Press Ctrl-C to stop.");
+
+    loop {
+        let Ok(first_event) = rx.recv() else {
+            return Ok(());
+        };
+        std::thread::sleep(Duration::from_millis(200));
+
+        let mut events = vec![first_event];
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+
+        let mut changed_paths = std::collections::HashSet::new();
+        for event in events.into_iter().flatten() {
+            if matches!(event.kind, notify::EventKind::Modify(_) | notify::EventKind::Create(_)) {
+                changed_paths.extend(event.paths);
+            }
+        }
+
+        if is_directory_mode {
+            for path in changed_paths {
+                if path.extension().and_then(std::ffi::OsStr::to_str) != Some(cfg.extension.as_str()) {
+                    continue;
+                }
+                match format_one_file(&path, cfg) {
+                    Ok(true) => eprintln!("reformatted {}", path.display()),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("error formatting {}: {}", path.display(), e),
+                }
+            }
+        } else if !changed_paths.is_empty() && cfg.in_place {
+            // Go through the change-detecting path rather than
+            // `format_single_stream`: that one rewrites unconditionally, and
+            // rewriting in place would re-trigger the very `Modify` event
+            // we're handling, reformatting forever.
+            match format_one_file(Path::new(&cfg.inputs[0]), cfg) {
+                Ok(true) => eprintln!("reformatted {}", cfg.inputs[0]),
+                Ok(false) => {}
+                Err(e) => eprintln!("error formatting {}: {}", cfg.inputs[0], e),
+            }
+        } else if !changed_paths.is_empty() {
+            match format_single_stream(&cfg.inputs[0], cfg) {
+                Ok(()) => eprintln!("reformatted {}", cfg.inputs[0]),
+                Err(e) => eprintln!("error formatting {}: {}", cfg.inputs[0], e),
+            }
+        }
+    }
+}
+
 fn render_indent(indent: &Indentation) -> String {
     use Indentation::{Spaces, Tabs};
     match indent {
@@ -285,3 +902,82 @@ fn main() {
         std::process::exit(1);
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{format_one_file, DataFormat, JSONFormatStyle, JfmtConfig};
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::path::PathBuf;
+
+    fn test_config(path: &str) -> JfmtConfig {
+        JfmtConfig {
+            inputs: vec![path.to_owned()],
+            output: None,
+            in_place: true,
+            check: false,
+            watch: false,
+            from: DataFormat::Json,
+            to: DataFormat::Json,
+            exclude: Vec::new(),
+            extension: "json".to_owned(),
+            json5: false,
+            no_trailing_commas: false,
+            sort_arrays: false,
+            sort_keys: false,
+            one_element_lines: false,
+            format: JSONFormatStyle::Pretty(super::Indentation::Spaces(2)),
+        }
+    }
+
+    /// Each test gets its own directory under the OS temp dir, named after the
+    /// test and the process id so parallel test runs don't collide.
+    fn fresh_test_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jfmt-test-{tag}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("create test dir");
+        dir
+    }
+
+    #[test]
+    fn in_place_format_through_symlink_rewrites_the_real_file() {
+        let dir = fresh_test_dir("symlink");
+        let real_path = dir.join("real.json");
+        let link_path = dir.join("link.json");
+        fs::write(&real_path, b"{\"a\":1}").expect("write real file");
+        symlink(&real_path, &link_path).expect("create symlink");
+
+        let cfg = test_config(link_path.to_str().unwrap());
+        let changed = format_one_file(&link_path, &cfg).expect("format through symlink");
+        assert!(changed);
+
+        // The link itself must still be a symlink to the same target: the
+        // rewrite should land on the real file, not replace the link with a
+        // plain file.
+        let metadata = fs::symlink_metadata(&link_path).expect("stat link");
+        assert!(metadata.file_type().is_symlink());
+        assert_eq!(fs::read_link(&link_path).unwrap(), real_path);
+
+        let contents = fs::read_to_string(&real_path).expect("read real file");
+        assert!(contents.contains("\"a\": 1"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn in_place_format_preserves_permission_bits() {
+        let dir = fresh_test_dir("perms");
+        let path = dir.join("mode.json");
+        fs::write(&path, b"{\"a\":1}").expect("write file");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).expect("chmod");
+
+        let cfg = test_config(path.to_str().unwrap());
+        let changed = format_one_file(&path, &cfg).expect("format file");
+        assert!(changed);
+
+        let mode = fs::metadata(&path).expect("stat file").permissions().mode() & 0o777;
+        assert_eq!(mode, 0o640);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}